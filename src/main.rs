@@ -1,15 +1,94 @@
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io;
 use std::io::Read;
 use std::io::Seek;
 use std::io::SeekFrom;
 
+use capstone::prelude::*;
 use pretty_hex::*;
+use serde::Serialize;
 use riscv_disasm::*;
 
+/// Everything that can go wrong while parsing a TBF image.
+#[derive(Debug)]
+enum TbfError {
+    Io(io::Error),
+    MissingFile,
+    MissingOptionValue(&'static str),
+    UnknownArch(String),
+    Disasm(String),
+    UnexpectedVersion(u16),
+    Serialize(serde_json::Error),
+    Underflow {
+        section: &'static str,
+        value: u64,
+        minimum: u64,
+    },
+    UnexpectedEof { section: &'static str, offset: u64 },
+    TlvOverrun {
+        tipe: u16,
+        length: u16,
+        remaining: u64,
+        offset: u64,
+    },
+}
+
+impl fmt::Display for TbfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TbfError::Io(e) => write!(f, "i/o error: {}", e),
+            TbfError::MissingFile => write!(f, "no file specified"),
+            TbfError::MissingOptionValue(opt) => write!(f, "{} requires a value", opt),
+            TbfError::UnknownArch(a) => write!(f, "unknown arch: {}", a),
+            TbfError::Disasm(e) => write!(f, "disassembly failed: {}", e),
+            TbfError::UnexpectedVersion(v) => write!(f, "unexpected TBF version {:#x}", v),
+            TbfError::Serialize(e) => write!(f, "failed to serialize output: {}", e),
+            TbfError::Underflow {
+                section,
+                value,
+                minimum,
+            } => write!(
+                f,
+                "{} value {:#x} is smaller than the minimum {:#x}",
+                section, value, minimum
+            ),
+            TbfError::UnexpectedEof { section, offset } => write!(
+                f,
+                "unexpected end of file while reading {} at offset {:#x}",
+                section, offset
+            ),
+            TbfError::TlvOverrun {
+                tipe,
+                length,
+                remaining,
+                offset,
+            } => write!(
+                f,
+                "TLV type {:#x} at offset {:#x} claims length {} but only {} header bytes remain",
+                tipe, offset, length, remaining
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TbfError {}
+
+impl From<io::Error> for TbfError {
+    fn from(e: io::Error) -> Self {
+        TbfError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for TbfError {
+    fn from(e: serde_json::Error) -> Self {
+        TbfError::Serialize(e)
+    }
+}
+
 #[repr(C)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize)]
 struct TbfHeaderV2Base {
     version: u16,
     header_size: u16,
@@ -26,26 +105,65 @@ enum TbfHeaderTypes {
     TbfHeaderMain = 1,
     TbfHeaderWriteableFlashRegions = 2,
     TbfHeaderPackageName = 3,
-    Unused = 5,
+    TbfHeaderFixedAddresses = 5,
+    TbfHeaderKernelVersion = 8,
+    TbfHeaderProgram = 9,
 }
 
 /// The TLV header (T and L).
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 struct TbfHeaderTlv {
-    tipe: TbfHeaderTypes,
+    tipe: u16,
     length: u16,
 }
 
 #[repr(C)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize)]
 struct TbfHeaderV2Main {
     init_fn_offset: u32,
     protected_size: u32,
     minimum_ram_size: u32,
 }
 
+/// Program header: a superset of Main that also records where the binary
+/// ends within the flash region and the application's version.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Serialize)]
+struct TbfHeaderV2Program {
+    init_fn_offset: u32,
+    protected_size: u32,
+    minimum_ram_size: u32,
+    binary_end_offset: u32,
+    app_version: u32,
+}
+
+/// One entry in a Writeable Flash Regions block.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Serialize)]
+struct TbfHeaderV2WriteableFlashRegion {
+    offset: u32,
+    size: u32,
+}
+
+/// Fixed addresses the app was compiled to run at.
 #[repr(C)]
+#[derive(Clone, Copy, Debug, Serialize)]
+struct TbfHeaderV2FixedAddresses {
+    fixed_address_ram: u32,
+    fixed_address_flash: u32,
+}
+
+/// The minimum kernel version this app requires.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Serialize)]
+struct TbfHeaderV2KernelVersion {
+    major: u16,
+    minor: u16,
+}
+
+#[repr(C)]
+#[derive(Serialize)]
 struct LayoutHeader32 {
     got_sym_start: u32,
     got_start: u32,
@@ -59,99 +177,547 @@ struct LayoutHeader32 {
     stack_size: u32,
 }
 
-fn read_tbf_tlv(reader: &mut dyn Read) -> Option<TbfHeaderTlv> {
-    let mut h = [0u8; std::mem::size_of::<TbfHeaderTlv>()];
-    match reader.read_exact(&mut h[..]) {
-        Ok(_) => Some(unsafe { std::mem::transmute(h) }),
-        _ => None,
+/// A decoded TLV block, tagged by its TBF type for serialization.
+#[derive(Serialize)]
+#[serde(tag = "type", content = "data")]
+enum TlvBlock {
+    Main(TbfHeaderV2Main),
+    Program(TbfHeaderV2Program),
+    WriteableFlashRegions(Vec<TbfHeaderV2WriteableFlashRegion>),
+    FixedAddresses(TbfHeaderV2FixedAddresses),
+    KernelVersion(TbfHeaderV2KernelVersion),
+    PackageName(String),
+    Unknown { tipe: u16, data: Vec<u8> },
+}
+
+/// A single decoded instruction in the code region.
+#[derive(Serialize)]
+struct Instruction {
+    pc: u64,
+    text: String,
+}
+
+/// The complete parsed TBF image, the in-memory model shared by the
+/// human-readable and JSON renderers.
+#[derive(Serialize)]
+struct TbfImage {
+    base: TbfHeaderV2Base,
+    checksum_computed: u32,
+    tlvs: Vec<TlvBlock>,
+    layout: LayoutHeader32,
+    instructions: Vec<Instruction>,
+    #[serde(skip)]
+    foot: Vec<u8>,
+}
+
+/// Read a single little-endian `u16` from `r`.
+fn read_u16(r: &mut dyn Read) -> io::Result<u16> {
+    let mut b = [0u8; 2];
+    r.read_exact(&mut b)?;
+    Ok(u16::from_le_bytes(b))
+}
+
+/// Read a single little-endian `u32` from `r`.
+fn read_u32(r: &mut dyn Read) -> io::Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+/// A header struct that can be decoded field-by-field from a reader with a
+/// fixed little-endian byte order, so the parser is correct regardless of
+/// host endianness and never reinterprets raw bytes as an enum. The TLV
+/// `tipe` is kept as a raw `u16` rather than an enum for exactly this reason:
+/// an unknown tag can never be undefined behaviour. Rather than erroring on
+/// unknown tags, the TLV loop preserves them as a hex-dumped `Unknown` block
+/// so real app binaries with newer block types still inspect cleanly.
+trait FromReader: Sized {
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self>;
+}
+
+impl FromReader for TbfHeaderV2Base {
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self> {
+        Ok(TbfHeaderV2Base {
+            version: read_u16(r)?,
+            header_size: read_u16(r)?,
+            total_size: read_u32(r)?,
+            flags: read_u32(r)?,
+            checksum: read_u32(r)?,
+        })
+    }
+}
+
+impl FromReader for TbfHeaderTlv {
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self> {
+        Ok(TbfHeaderTlv {
+            tipe: read_u16(r)?,
+            length: read_u16(r)?,
+        })
     }
 }
 
-fn read_tbf_main(reader: &mut dyn Read) -> Option<TbfHeaderV2Main> {
-    let mut h = [0u8; std::mem::size_of::<TbfHeaderV2Main>()];
-    match reader.read_exact(&mut h[..]) {
-        Ok(_) => Some(unsafe { std::mem::transmute(h) }),
-        _ => None,
+impl FromReader for TbfHeaderV2Main {
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self> {
+        Ok(TbfHeaderV2Main {
+            init_fn_offset: read_u32(r)?,
+            protected_size: read_u32(r)?,
+            minimum_ram_size: read_u32(r)?,
+        })
     }
 }
 
-fn read_layout_header32(reader: &mut dyn Read) -> Option<LayoutHeader32> {
-    let mut h = [0u8; std::mem::size_of::<LayoutHeader32>()];
-    match reader.read_exact(&mut h[..]) {
-        Ok(_) => Some(unsafe { std::mem::transmute(h) }),
-        _ => None,
+impl FromReader for TbfHeaderV2Program {
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self> {
+        Ok(TbfHeaderV2Program {
+            init_fn_offset: read_u32(r)?,
+            protected_size: read_u32(r)?,
+            minimum_ram_size: read_u32(r)?,
+            binary_end_offset: read_u32(r)?,
+            app_version: read_u32(r)?,
+        })
     }
 }
 
-fn main() -> io::Result<()> {
-    let mut file = File::open(env::args().nth(1).expect("no file specified")).expect("foo");
+impl FromReader for TbfHeaderV2WriteableFlashRegion {
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self> {
+        Ok(TbfHeaderV2WriteableFlashRegion {
+            offset: read_u32(r)?,
+            size: read_u32(r)?,
+        })
+    }
+}
+
+impl FromReader for TbfHeaderV2FixedAddresses {
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self> {
+        Ok(TbfHeaderV2FixedAddresses {
+            fixed_address_ram: read_u32(r)?,
+            fixed_address_flash: read_u32(r)?,
+        })
+    }
+}
+
+impl FromReader for TbfHeaderV2KernelVersion {
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self> {
+        Ok(TbfHeaderV2KernelVersion {
+            major: read_u16(r)?,
+            minor: read_u16(r)?,
+        })
+    }
+}
+
+impl FromReader for LayoutHeader32 {
+    fn from_reader(r: &mut dyn Read) -> io::Result<Self> {
+        Ok(LayoutHeader32 {
+            got_sym_start: read_u32(r)?,
+            got_start: read_u32(r)?,
+            got_size: read_u32(r)?,
+            data_sym_start: read_u32(r)?,
+            data_start: read_u32(r)?,
+            data_size: read_u32(r)?,
+            bss_start: read_u32(r)?,
+            bss_size: read_u32(r)?,
+            reldata_start: read_u32(r)?,
+            stack_size: read_u32(r)?,
+        })
+    }
+}
+
+/// Compute the TBF header checksum over `bytes` (the entire header region,
+/// from offset 0 through `header_size`). Each little-endian `u32` word is
+/// XORed together, substituting zero for the word that holds the `checksum`
+/// field itself (word index 3 of the base header).
+fn header_checksum(bytes: &[u8]) -> u32 {
+    let mut sum = 0_u32;
+    for (i, chunk) in bytes.chunks(4).enumerate() {
+        let word = if i == 3 {
+            0
+        } else {
+            let mut w = [0u8; 4];
+            w[..chunk.len()].copy_from_slice(chunk);
+            u32::from_le_bytes(w)
+        };
+        sum ^= word;
+    }
+    sum
+}
+
+/// The instruction set used to decode the code region.
+#[derive(Clone, Copy)]
+enum Arch {
+    Rv32,
+    Rv64,
+    Thumb,
+}
+
+/// Decode the code region into instructions using the selected architecture.
+/// `pc` is the address of the first byte in the flashed image so listings
+/// line up with the real program counter.
+fn disassemble(arch: Arch, code: &[u8], pc: u64) -> Result<Vec<Instruction>, TbfError> {
+    match arch {
+        Arch::Rv32 => Ok(Disassembler::new(rv_isa::rv32, code, pc)
+            .map(|d| Instruction {
+                pc: d.pc as u64,
+                text: format_inst(32, &d),
+            })
+            .collect()),
+        Arch::Rv64 => Ok(Disassembler::new(rv_isa::rv64, code, pc)
+            .map(|d| Instruction {
+                pc: d.pc as u64,
+                text: format_inst(64, &d),
+            })
+            .collect()),
+        Arch::Thumb => {
+            let cs = Capstone::new()
+                .arm()
+                .mode(arch::arm::ArchMode::Thumb)
+                .detail(false)
+                .build()
+                .map_err(|e| TbfError::Disasm(e.to_string()))?;
+            let insns = cs
+                .disasm_all(code, pc)
+                .map_err(|e| TbfError::Disasm(e.to_string()))?;
+            Ok(insns
+                .iter()
+                .map(|i| Instruction {
+                    pc: i.address(),
+                    text: format!(
+                        "{} {}",
+                        i.mnemonic().unwrap_or_default(),
+                        i.op_str().unwrap_or_default()
+                    )
+                    .trim_end()
+                    .to_string(),
+                })
+                .collect())
+        }
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run() -> Result<(), TbfError> {
+    let mut format = String::from("human");
+    let mut arch = Arch::Rv32;
+    let mut path: Option<String> = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                format = args.next().ok_or(TbfError::MissingOptionValue("--format"))?
+            }
+            "--arch" => {
+                let value = args.next().ok_or(TbfError::MissingOptionValue("--arch"))?;
+                arch = match value.as_str() {
+                    "rv32" => Arch::Rv32,
+                    "rv64" => Arch::Rv64,
+                    "thumb" => Arch::Thumb,
+                    _ => return Err(TbfError::UnknownArch(value)),
+                }
+            }
+            _ => path = Some(arg),
+        }
+    }
+
+    let mut file = File::open(path.ok_or(TbfError::MissingFile)?)?;
 
     let mut buf = vec![0_u8; 2];
 
-    file.read_exact(&mut buf[0..2]).expect("ok");
+    file.read_exact(&mut buf[0..2]).map_err(|_| TbfError::UnexpectedEof {
+        section: "version",
+        offset: 0,
+    })?;
 
     let version = buf[0] as u16 | (buf[1] as u16) << 8;
 
     file.seek(SeekFrom::Start(0))?;
 
-    match version {
-        2 => tbf_v2(&mut file),
-        _ => panic!("unexpected version"),
+    let image = match version {
+        2 => tbf_v2(&mut file, arch)?,
+        _ => return Err(TbfError::UnexpectedVersion(version)),
+    };
+
+    match format.as_str() {
+        "json" => println!("{}", serde_json::to_string_pretty(&image)?),
+        _ => print_human(&image),
     }
 
     Ok(())
 }
 
-fn tbf_v2(mut file: &mut dyn Read) {
+fn tbf_v2(mut file: &mut dyn Read, arch: Arch) -> Result<TbfImage, TbfError> {
     let header_size = std::mem::size_of::<TbfHeaderV2Base>();
     let mut hb = vec![0_u8; header_size];
 
-    file.read_exact(&mut hb[0..header_size]).expect("ok");
+    file.read_exact(&mut hb[0..header_size])
+        .map_err(|_| TbfError::UnexpectedEof {
+            section: "base header",
+            offset: 0,
+        })?;
 
-    let (_head, body, _tail) = unsafe { hb.align_to::<TbfHeaderV2Base>() };
-    let header = &body[0];
+    let base = TbfHeaderV2Base::from_reader(&mut std::io::Cursor::new(&hb)).map_err(|_| {
+        TbfError::UnexpectedEof {
+            section: "base header",
+            offset: 0,
+        }
+    })?;
 
-    println!("version          {:x?}", header.version);
-    println!("header_size      {:x?}", header.header_size);
-    println!("total_size       {:x?}", header.total_size);
-    println!("flags            {:x?}", header.flags);
-    println!("checksum         {:x?}", header.checksum);
-    println!("");
+    // A header that claims to be smaller than the base header is malformed;
+    // reject it before the subtraction below can underflow.
+    if (base.header_size as usize) < header_size {
+        return Err(TbfError::Underflow {
+            section: "header_size",
+            value: base.header_size as u64,
+            minimum: header_size as u64,
+        });
+    }
 
+    // Slurp the remaining header bytes so we can both verify the checksum
+    // over the whole header region and parse the TLVs from the buffer.
+    let rest_len = base.header_size as usize - header_size;
+    let mut rest = vec![0_u8; rest_len];
+    file.read_exact(&mut rest)
+        .map_err(|_| TbfError::UnexpectedEof {
+            section: "header TLVs",
+            offset: header_size as u64,
+        })?;
+
+    let mut full = hb.clone();
+    full.extend_from_slice(&rest);
+    let checksum_computed = header_checksum(&full);
+
+    let mut tlvs = Vec::new();
     let mut padding = 0_u64;
 
-    let mut r =
-        file.take(header.header_size as u64 - std::mem::size_of::<TbfHeaderV2Base>() as u64);
-    loop {
-        match read_tbf_tlv(&mut r) {
-            Some(tlv) => {
-                println!("type             {:x?}", tlv.tipe);
-                println!("length           {:x?}", tlv.length);
-                match tlv.tipe {
-                    TbfHeaderTypes::TbfHeaderMain => {
-                        let h = read_tbf_main(&mut r).expect("ok");
-                        println!("init_fn_offset   {:x?}", h.init_fn_offset);
-                        println!("protected_size   {:x?}", h.protected_size);
-                        println!("minimum_ram_size {:x?}", h.minimum_ram_size);
-
-                        padding = padding + h.protected_size as u64;
+    let rest_len = rest_len as u64;
+    let mut r = std::io::Cursor::new(rest);
+    // Walk the TLVs while at least a TLV header (4 bytes) remains; a short
+    // trailer is treated as the end of the header region.
+    while rest_len - r.position() >= 4 {
+        let offset = header_size as u64 + r.position();
+        let tlv = TbfHeaderTlv::from_reader(&mut r).map_err(|_| TbfError::UnexpectedEof {
+            section: "TLV header",
+            offset,
+        })?;
+
+        // The declared length must not run past the remaining header bytes.
+        let remaining = rest_len - r.position();
+        if tlv.length as u64 > remaining {
+            return Err(TbfError::TlvOverrun {
+                tipe: tlv.tipe,
+                length: tlv.length,
+                remaining,
+                offset,
+            });
+        }
+
+        let body_pos = r.position();
+        let body_offset = header_size as u64 + body_pos;
+        match tlv.tipe {
+            t if t == TbfHeaderTypes::TbfHeaderMain as u16 => {
+                let h = TbfHeaderV2Main::from_reader(&mut r).map_err(|_| {
+                    TbfError::UnexpectedEof {
+                        section: "main header",
+                        offset: body_offset,
                     }
-                    TbfHeaderTypes::TbfHeaderPackageName => {
-                        let s = ss(&mut r, tlv.length.into());
-                        println!("package name     {}", s);
+                })?;
+                padding += h.protected_size as u64;
+                tlvs.push(TlvBlock::Main(h));
+            }
+            t if t == TbfHeaderTypes::TbfHeaderProgram as u16 => {
+                let h = TbfHeaderV2Program::from_reader(&mut r).map_err(|_| {
+                    TbfError::UnexpectedEof {
+                        section: "program header",
+                        offset: body_offset,
                     }
-                    _ => {}
+                })?;
+                padding += h.protected_size as u64;
+                tlvs.push(TlvBlock::Program(h));
+            }
+            t if t == TbfHeaderTypes::TbfHeaderWriteableFlashRegions as u16 => {
+                let mut regions = Vec::new();
+                for _ in 0..tlv.length / 8 {
+                    regions.push(
+                        TbfHeaderV2WriteableFlashRegion::from_reader(&mut r).map_err(|_| {
+                            TbfError::UnexpectedEof {
+                                section: "writeable flash region",
+                                offset: body_offset,
+                            }
+                        })?,
+                    );
                 }
-                println!("");
+                tlvs.push(TlvBlock::WriteableFlashRegions(regions));
+            }
+            t if t == TbfHeaderTypes::TbfHeaderFixedAddresses as u16 => {
+                let h = TbfHeaderV2FixedAddresses::from_reader(&mut r).map_err(|_| {
+                    TbfError::UnexpectedEof {
+                        section: "fixed addresses",
+                        offset: body_offset,
+                    }
+                })?;
+                tlvs.push(TlvBlock::FixedAddresses(h));
+            }
+            t if t == TbfHeaderTypes::TbfHeaderKernelVersion as u16 => {
+                let h = TbfHeaderV2KernelVersion::from_reader(&mut r).map_err(|_| {
+                    TbfError::UnexpectedEof {
+                        section: "kernel version",
+                        offset: body_offset,
+                    }
+                })?;
+                tlvs.push(TlvBlock::KernelVersion(h));
+            }
+            t if t == TbfHeaderTypes::TbfHeaderPackageName as u16 => {
+                tlvs.push(TlvBlock::PackageName(ss(&mut r, tlv.length.into())));
+            }
+            _ => {
+                let mut data = Vec::<u8>::new();
+                (&mut r)
+                    .take(tlv.length as u64)
+                    .read_to_end(&mut data)
+                    .map_err(|_| TbfError::UnexpectedEof {
+                        section: "unknown TLV body",
+                        offset: body_offset,
+                    })?;
+                tlvs.push(TlvBlock::Unknown {
+                    tipe: tlv.tipe,
+                    data,
+                });
             }
-            None => break, // no more sections
         }
+
+        // Re-sync to the declared length so trailing padding (or a block
+        // whose struct is smaller than its length) does not misalign the
+        // next TLV. The overrun check above guarantees this stays in bounds.
+        r.set_position(body_pos + tlv.length as u64);
+    }
+
+    let _header_padding = file.take(padding).read_to_end(&mut Vec::new())?;
+
+    let layout = LayoutHeader32::from_reader(&mut file).map_err(|_| TbfError::UnexpectedEof {
+        section: "layout header",
+        offset: base.header_size as u64,
+    })?;
+
+    let layout_size = std::mem::size_of::<LayoutHeader32>() as u64;
+
+    // `got_sym_start` is measured from the start of the layout header, so a
+    // value below the layout size would make the code region negative.
+    if (layout.got_sym_start as u64) < layout_size {
+        return Err(TbfError::Underflow {
+            section: "got_sym_start",
+            value: layout.got_sym_start as u64,
+            minimum: layout_size,
+        });
     }
 
-    let _header_remnants = r.read_to_end(&mut Vec::new());
-    let _header_padding = file.take(padding).read_to_end(&mut Vec::new());
+    let mut buffer = Vec::<u8>::new();
+    file.take(layout.got_sym_start as u64 - layout_size)
+        .read_to_end(&mut buffer)
+        .map_err(|_| TbfError::UnexpectedEof {
+            section: "code region",
+            offset: base.header_size as u64 + layout_size,
+        })?;
+
+    let instructions = disassemble(arch, &buffer, layout_size + base.header_size as u64)?;
+
+    let mut foot = Vec::<u8>::new();
+    file.read_to_end(&mut foot)?;
+
+    Ok(TbfImage {
+        base,
+        checksum_computed,
+        tlvs,
+        layout,
+        instructions,
+        foot,
+    })
+}
+
+/// Render a parsed image as the default human-readable dump.
+fn print_human(image: &TbfImage) {
+    let h = &image.base;
+    println!("version          {:x?}", h.version);
+    println!("header_size      {:x?}", h.header_size);
+    println!("total_size       {:x?}", h.total_size);
+    println!("flags            {:x?}", h.flags);
+    println!("checksum         {:x?}", h.checksum);
+    if image.checksum_computed == h.checksum {
+        println!("checksum         OK");
+    } else {
+        println!(
+            "checksum         MISMATCH expected {:x?} computed {:x?}",
+            h.checksum, image.checksum_computed
+        );
+    }
+    println!();
 
-    let layout = read_layout_header32(&mut file).expect("ok");
+    for tlv in &image.tlvs {
+        match tlv {
+            TlvBlock::Main(m) => {
+                println!("type             {:x?}", TbfHeaderTypes::TbfHeaderMain as u16);
+                println!("init_fn_offset   {:x?}", m.init_fn_offset);
+                println!("protected_size   {:x?}", m.protected_size);
+                println!("minimum_ram_size {:x?}", m.minimum_ram_size);
+            }
+            TlvBlock::Program(p) => {
+                println!(
+                    "type             {:x?}",
+                    TbfHeaderTypes::TbfHeaderProgram as u16
+                );
+                println!("init_fn_offset   {:x?}", p.init_fn_offset);
+                println!("protected_size   {:x?}", p.protected_size);
+                println!("minimum_ram_size {:x?}", p.minimum_ram_size);
+                println!("binary_end_offset {:x?}", p.binary_end_offset);
+                println!("app_version      {:x?}", p.app_version);
+            }
+            TlvBlock::WriteableFlashRegions(regions) => {
+                println!(
+                    "type             {:x?}",
+                    TbfHeaderTypes::TbfHeaderWriteableFlashRegions as u16
+                );
+                for (i, wfr) in regions.iter().enumerate() {
+                    println!("region {}", i);
+                    println!("  offset         {:x?}", wfr.offset);
+                    println!("  size           {:x?}", wfr.size);
+                }
+            }
+            TlvBlock::FixedAddresses(f) => {
+                println!(
+                    "type             {:x?}",
+                    TbfHeaderTypes::TbfHeaderFixedAddresses as u16
+                );
+                println!("fixed_addr_ram   {:x?}", f.fixed_address_ram);
+                println!("fixed_addr_flash {:x?}", f.fixed_address_flash);
+            }
+            TlvBlock::KernelVersion(k) => {
+                println!(
+                    "type             {:x?}",
+                    TbfHeaderTypes::TbfHeaderKernelVersion as u16
+                );
+                println!("kernel major     {:x?}", k.major);
+                println!("kernel minor     {:x?}", k.minor);
+            }
+            TlvBlock::PackageName(s) => {
+                println!(
+                    "type             {:x?}",
+                    TbfHeaderTypes::TbfHeaderPackageName as u16
+                );
+                println!("package name     {}", s);
+            }
+            TlvBlock::Unknown { tipe, data } => {
+                println!("type             {:x?}", tipe);
+                println!("{:?}", data.hex_dump());
+            }
+        }
+        println!();
+    }
+
+    let layout = &image.layout;
     println!("got_sym_start    {:x}", layout.got_sym_start);
     println!("got_start        {:x}", layout.got_start);
     println!("got_size         {:x}", layout.got_size);
@@ -163,31 +729,17 @@ fn tbf_v2(mut file: &mut dyn Read) {
     println!("reldata_start    {:x}", layout.reldata_start);
     println!("stack_size       {:x}", layout.stack_size);
 
-    println!("");
-
-    let layout_size = std::mem::size_of::<LayoutHeader32>() as u64;
-
-    let mut buffer = Vec::<u8>::new();
-    file.take(layout.got_sym_start as u64 - layout_size)
-        .read_to_end(&mut buffer)
-        .expect("read failed");
+    println!();
 
-    for decoded in Disassembler::new(
-        rv_isa::rv32,
-        &buffer,
-        layout_size + header.header_size as u64,
-    ) {
-        println!("{:08x} {}", decoded.pc, format_inst(32, &decoded));
+    for inst in &image.instructions {
+        println!("{:08x} {}", inst.pc, inst.text);
     }
 
-    buffer = Vec::<u8>::new();
-    file.read_to_end(&mut buffer).expect("read failed");
-    println!("{:?}", buffer.hex_dump());
+    println!("{:?}", image.foot.hex_dump());
 }
 
 fn ss(file: &mut dyn Read, len: u64) -> String {
-    let mut r = file.take(len);
-    let mut buf = String::new();
-    let _ = r.read_to_string(&mut buf);
-    buf
+    let mut buf = Vec::new();
+    let _ = file.take(len).read_to_end(&mut buf);
+    String::from_utf8_lossy(&buf).into_owned()
 }